@@ -1,4 +1,6 @@
-use btleplug::api::{BDAddr, Central, CentralEvent, Peripheral, ValueNotification, UUID};
+use btleplug::api::{
+    BDAddr, Central, CentralEvent, Characteristic, Peripheral, ValueNotification, UUID,
+};
 #[cfg(target_os = "linux")]
 use btleplug::bluez::{adapter::ConnectedAdapter, manager::Manager};
 #[cfg(target_os = "macos")]
@@ -10,11 +12,12 @@ use clap::{App, AppSettings, Arg, SubCommand};
 
 use std::{
     collections::HashMap,
-    io::{stdout, Write},
+    fs::OpenOptions,
+    io::{stdout, BufRead, Write},
     str::FromStr,
-    sync::{atomic::AtomicBool, Arc},
+    sync::{atomic::AtomicBool, Arc, Mutex},
     thread,
-    time::Duration,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use crossbeam_channel::{self as c_channel, unbounded};
@@ -23,16 +26,115 @@ use thiserror::Error;
 
 use crossterm::{
     queue,
-    style::{Colorize, Print, PrintStyledContent},
+    style::{Colorize, Print, PrintStyledContent, StyledContent},
 };
 
-use dialoguer::theme::CustomPromptCharacterTheme;
+use regex::Regex;
+
+use rustyline::{
+    completion::{Completer, Pair},
+    highlight::Highlighter,
+    hint::Hinter,
+    history::DefaultHistory,
+    validate::Validator,
+    Context, Editor, ExternalPrinter, Helper,
+};
 
 // 0000ffe1-0000-1000-8000-00805f9b34fb
 const UUID_NOTIFY: UUID = UUID::B128([
     0xfb, 0x34, 0x9b, 0x5f, 0x80, 0x00, 0x00, 0x80, 0x00, 0x10, 0x00, 0x00, 0xe1, 0xff, 0x00, 0x00,
 ]);
 
+// 6e400002-b5a3-f393-e0a9-e50e24dcca9e (Nordic UART Service, TX: host -> device)
+const UUID_NUS_TX: UUID = UUID::B128([
+    0x9e, 0xca, 0xdc, 0x24, 0x0e, 0xe5, 0xa9, 0xe0, 0x93, 0xf3, 0xa3, 0xb5, 0x02, 0x00, 0x40, 0x6e,
+]);
+
+// 6e400003-b5a3-f393-e0a9-e50e24dcca9e (Nordic UART Service, RX: device -> host)
+const UUID_NUS_RX: UUID = UUID::B128([
+    0x9e, 0xca, 0xdc, 0x24, 0x0e, 0xe5, 0xa9, 0xe0, 0x93, 0xf3, 0xa3, 0xb5, 0x03, 0x00, 0x40, 0x6e,
+]);
+
+/// A known serial-over-BLE profile: which characteristic to subscribe to for
+/// inbound data, and which to write outbound data to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ProfileKind {
+    /// HM-10/HM-11 style modules: a single characteristic used for both
+    /// directions.
+    Hm,
+    /// Nordic UART Service: distinct TX/RX characteristics.
+    Nus,
+}
+
+impl ProfileKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ProfileKind::Hm => "hm",
+            ProfileKind::Nus => "nus",
+        }
+    }
+}
+
+impl FromStr for ProfileKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "hm" => Ok(ProfileKind::Hm),
+            "nus" => Ok(ProfileKind::Nus),
+            _ => Err(Error::UnknownProfile(s.to_string())),
+        }
+    }
+}
+
+struct Profile {
+    kind: ProfileKind,
+    notify: UUID,
+    write: UUID,
+}
+
+const PROFILES: [Profile; 2] = [
+    Profile {
+        kind: ProfileKind::Hm,
+        notify: UUID_NOTIFY,
+        write: UUID_NOTIFY,
+    },
+    Profile {
+        kind: ProfileKind::Nus,
+        notify: UUID_NUS_RX,
+        write: UUID_NUS_TX,
+    },
+];
+
+/// A profile that has been matched against a device's discovered
+/// characteristics, with the concrete characteristics to use.
+struct MatchedProfile {
+    kind: ProfileKind,
+    notify: Characteristic,
+    write: Characteristic,
+}
+
+/// Matches `characteristics` against the known profiles, optionally
+/// restricted to `forced`, and returns the first one for which both the
+/// notify and write characteristics are present.
+fn match_profile(
+    characteristics: &[Characteristic],
+    forced: Option<ProfileKind>,
+) -> Option<MatchedProfile> {
+    PROFILES
+        .iter()
+        .filter(|profile| forced.map_or(true, |kind| profile.kind == kind))
+        .find_map(|profile| {
+            let notify = characteristics.iter().find(|c| c.uuid == profile.notify)?;
+            let write = characteristics.iter().find(|c| c.uuid == profile.write)?;
+            Some(MatchedProfile {
+                kind: profile.kind,
+                notify: notify.clone(),
+                write: write.clone(),
+            })
+        })
+}
+
 #[derive(Error, Debug)]
 enum Error {
     #[error("Bluetooth error: {0}")]
@@ -49,8 +151,26 @@ enum Error {
     AdapterStopped,
     #[error("IO error")]
     IOError(#[from] std::io::Error),
-    #[error("Device is not a HM device")]
-    NotHMDevice,
+    #[error("No matching UART profile found (searched: {0})")]
+    NoMatchingProfile(String),
+    #[error("Unknown profile: {0}")]
+    UnknownProfile(String),
+    #[error("Invalid name filter: {0}")]
+    InvalidNameFilter(#[from] regex::Error),
+    #[error("Invalid --min-rssi value: {0}")]
+    InvalidRssi(#[from] std::num::ParseIntError),
+    #[error("Invalid value for {0}: {1}")]
+    InvalidInteger(&'static str, std::num::ParseIntError),
+    #[error("Readline error: {0}")]
+    Readline(#[from] rustyline::error::ReadlineError),
+    #[error("Device disconnected during script execution")]
+    ScriptDisconnected,
+    #[error("Command `{0}` elicited an ERROR response")]
+    ScriptCommandError(String),
+    #[error("Gave up reconnecting to the device")]
+    ReconnectFailed,
+    #[error("Reconnect cancelled by user")]
+    ReconnectCancelled,
     #[error("Unknown error")]
     Unknown,
 }
@@ -96,13 +216,70 @@ fn addr_to_string<P: Peripheral, C: Central<P>>(central: &C, addr: BDAddr) -> St
     )
 }
 
+/// Colors an RSSI reading so weak/strong signals are visually obvious at a
+/// glance: green when close, yellow in range, red when marginal.
+fn rssi_badge(rssi: Option<i8>) -> StyledContent<String> {
+    match rssi {
+        Some(rssi) => {
+            let text = format!("{} dBm", rssi);
+            if rssi >= -60 {
+                text.green()
+            } else if rssi >= -80 {
+                text.yellow()
+            } else {
+                text.red()
+            }
+        }
+        None => "? dBm".to_string().dark_grey(),
+    }
+}
+
+/// Prints manufacturer data and advertised service UUIDs for `addr` when
+/// `--verbose` is set.
+fn print_verbose_detail<P: Peripheral, C: Central<P>>(
+    stdout: &mut std::io::Stdout,
+    central: &C,
+    addr: BDAddr,
+) -> Result<(), Error> {
+    let device = central.peripheral(addr).unwrap();
+    let properties = device.properties();
+    if let Some(manufacturer_data) = properties.manufacturer_data {
+        queue!(
+            stdout,
+            Print("    manufacturer data: "),
+            Print(format!("{:x?}", manufacturer_data)),
+            Print("\n"),
+        )?;
+    }
+    if !properties.services.is_empty() {
+        queue!(
+            stdout,
+            Print("    services: "),
+            Print(
+                properties
+                    .services
+                    .iter()
+                    .map(|uuid| uuid.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Print("\n"),
+        )?;
+    }
+    Ok(())
+}
+
 #[derive(Debug, PartialEq, Eq)]
 enum DeviceStatus {
     Discovered,
     Updated,
 }
 
-fn run_scan(verbose: bool, filter_unnamed: bool) -> Result<(), Error> {
+fn run_scan(
+    verbose: bool,
+    min_rssi: Option<i8>,
+    name_filter: Option<Regex>,
+) -> Result<(), Error> {
     let manager = Manager::new()?;
     let central = get_central(&manager)?;
     let mut stdout = stdout();
@@ -124,12 +301,16 @@ fn run_scan(verbose: bool, filter_unnamed: bool) -> Result<(), Error> {
             CentralEvent::DeviceDiscovered(addr) => {
                 device_status.insert(addr, DeviceStatus::Discovered);
                 if verbose {
+                    let properties = central.peripheral(addr).unwrap().properties();
                     queue!(
                         stdout,
                         PrintStyledContent("[ADVERTISED] ".blue()),
                         Print(addr.to_string()),
+                        Print(" "),
+                        PrintStyledContent(rssi_badge(properties.rssi)),
                         Print("\n"),
                     )?;
+                    print_verbose_detail(&mut stdout, &central, addr)?;
                     stdout.flush()?;
                 }
             }
@@ -144,26 +325,39 @@ fn run_scan(verbose: bool, filter_unnamed: bool) -> Result<(), Error> {
                 device_status.remove(&addr);
             }
             CentralEvent::DeviceUpdated(addr) => {
+                let properties = central.peripheral(addr).unwrap().properties();
                 if verbose {
                     queue!(
                         stdout,
                         PrintStyledContent("[UPDATE] ".yellow()),
                         Print(addr_to_string(&central, addr)),
+                        Print(" "),
+                        PrintStyledContent(rssi_badge(properties.rssi)),
                         Print("\n"),
                     )?;
+                    print_verbose_detail(&mut stdout, &central, addr)?;
                     stdout.flush()?;
                 }
                 let status = device_status
                     .entry(addr)
                     .or_insert(DeviceStatus::Discovered);
                 if *status == DeviceStatus::Discovered {
-                    let device = central.peripheral(addr).unwrap();
-                    let name = device.properties().local_name;
-                    if name.is_some() || !filter_unnamed {
+                    let passes_rssi = min_rssi.map_or(true, |threshold| {
+                        properties.rssi.map_or(false, |rssi| rssi >= threshold)
+                    });
+                    let passes_name = name_filter.as_ref().map_or(true, |re| {
+                        properties
+                            .local_name
+                            .as_deref()
+                            .map_or(false, |name| re.is_match(name))
+                    });
+                    if passes_rssi && passes_name {
                         queue!(
                             stdout,
                             PrintStyledContent("[NEW] ".green()),
                             Print(addr_to_string(&central, addr)),
+                            Print(" "),
+                            PrintStyledContent(rssi_badge(properties.rssi)),
                             Print("\n"),
                         )?;
                         stdout.flush()?;
@@ -203,37 +397,103 @@ fn create_ctrlc_channel() -> Result<c_channel::Receiver<()>, Error> {
     Ok(receiver)
 }
 
+// Common HM AT commands offered on Tab.
+const AT_COMMANDS: &[&str] = &[
+    "AT", "AT+NAME", "AT+BAUD", "AT+ROLE", "AT+RESET", "AT+PIN", "AT+TYPE", "AT+IMME", "AT+VERSION",
+];
+
+/// A `rustyline` helper that completes common HM AT commands. Hinting and
+/// highlighting are left at their defaults; validation stays in the prompt
+/// loop so the same "AT command or quit" rule as before applies.
+struct AtCommandHelper;
+
+impl Completer for AtCommandHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let prefix = &line[..pos];
+        let candidates = AT_COMMANDS
+            .iter()
+            .filter(|cmd| cmd.starts_with(prefix))
+            .map(|cmd| Pair {
+                display: cmd.to_string(),
+                replacement: cmd.to_string(),
+            })
+            .collect();
+        Ok((0, candidates))
+    }
+}
+
+impl Hinter for AtCommandHelper {
+    type Hint = String;
+}
+
+impl Highlighter for AtCommandHelper {}
+
+impl Validator for AtCommandHelper {}
+
+impl Helper for AtCommandHelper {}
+
+/// Where command history is persisted between sessions.
+fn history_path() -> std::path::PathBuf {
+    dirs::home_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join(".hm-remote_history")
+}
+
+/// Spawns the thread that owns the `rustyline` editor and reads commands
+/// from the user. Returns a channel of completed commands together with an
+/// `ExternalPrinter` that lets other threads (e.g. the BLE notification
+/// callback) print lines without garbling the in-progress prompt: printing
+/// through it clears the current prompt line, writes the message, then
+/// redraws the prompt and whatever the user had typed so far.
 fn create_prompt_channel(
     sync_receiver: c_channel::Receiver<()>,
-) -> c_channel::Receiver<Result<String, Error>> {
+) -> Result<
+    (
+        c_channel::Receiver<Result<String, Error>>,
+        Box<dyn ExternalPrinter + Send>,
+    ),
+    Error,
+> {
+    let mut editor = Editor::<AtCommandHelper, DefaultHistory>::new()?;
+    editor.set_helper(Some(AtCommandHelper));
+    let history_path = history_path();
+    let _ = editor.load_history(&history_path);
+    let printer: Box<dyn ExternalPrinter + Send> = Box::new(editor.create_external_printer()?);
+
     let (sender, receiver) = unbounded();
-    thread::spawn(move || loop {
-        let input = dialoguer::Input::<String>::with_theme(&CustomPromptCharacterTheme::new(' '))
-            .with_prompt(">")
-            .validate_with(|input: &str| -> Result<(), &str> {
-                if !input.starts_with("AT") && input != "quit" {
-                    Err("Invalid Input, can only be AT command or quit")
-                } else {
-                    Ok(())
+    thread::spawn(move || {
+        let mut editor = editor;
+        loop {
+            match editor.readline("> ") {
+                Ok(command) => {
+                    if command != "quit" && !command.starts_with("AT") {
+                        println!("Invalid input, can only be AT command or quit");
+                        continue;
+                    }
+                    let _ = editor.add_history_entry(command.as_str());
+                    if sender.send(Ok(command)).is_err() {
+                        break;
+                    }
                 }
-            })
-            .interact();
-        match input {
-            Ok(command) => {
-                if sender.send(Ok(command)).is_err() {
+                Err(err) => {
+                    let _ = sender.send(Err(Error::from(err)));
                     break;
                 }
             }
-            Err(err) => {
-                let _ = sender.send(Err(Error::from(err)));
+            if sync_receiver.recv().is_err() {
                 break;
             }
         }
-        if sync_receiver.recv().is_err() {
-            break;
-        }
+        let _ = editor.save_history(&history_path);
     });
-    receiver
+    Ok((receiver, printer))
 }
 
 fn find_device<P: Peripheral, C: Central<P>>(
@@ -275,10 +535,204 @@ fn keep_connect<P: Peripheral>(device: &P) -> Result<(), Error> {
     Ok(())
 }
 
-fn run_console<P: Peripheral>(
+/// Serialization used by `--log-format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LogFormat {
+    Text,
+    Jsonl,
+}
+
+impl FromStr for LogFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "jsonl" => Ok(LogFormat::Jsonl),
+            _ => Err(Error::Unknown),
+        }
+    }
+}
+
+/// Settings for `--log`.
+struct LogConfig {
+    path: std::path::PathBuf,
+    format: LogFormat,
+}
+
+/// Which side of the link a logged line came from.
+#[derive(Debug, Clone, Copy)]
+enum LogDirection {
+    /// Host -> device.
+    Out,
+    /// Device -> host.
+    In,
+}
+
+/// Appends timestamped command/notification lines to a session log,
+/// independent of what is printed to the terminal. Every line carries both
+/// a monotonic (session-relative) and a wallclock timestamp, so a flaky
+/// module's replies can be correlated with what was sent even across long
+/// sessions.
+struct SessionLogger {
+    writer: std::fs::File,
+    format: LogFormat,
+    start: Instant,
+    start_wall: SystemTime,
+}
+
+impl SessionLogger {
+    fn new(path: &std::path::Path, format: LogFormat) -> Result<Self, Error> {
+        let writer = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(SessionLogger {
+            writer,
+            format,
+            start: Instant::now(),
+            start_wall: SystemTime::now(),
+        })
+    }
+
+    fn log(&mut self, dir: LogDirection, data: &str) -> Result<(), Error> {
+        let mono = self.start.elapsed().as_secs_f64();
+        let wall = (self.start_wall + self.start.elapsed())
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs_f64();
+        match self.format {
+            LogFormat::Text => {
+                let marker = match dir {
+                    LogDirection::Out => ">",
+                    LogDirection::In => "<",
+                };
+                writeln!(self.writer, "[{:.3} {:.3}] {} {}", wall, mono, marker, data)?;
+            }
+            LogFormat::Jsonl => {
+                let dir = match dir {
+                    LogDirection::Out => "out",
+                    LogDirection::In => "in",
+                };
+                let mut escaped = String::with_capacity(data.len());
+                for c in data.chars() {
+                    match c {
+                        '\\' => escaped.push_str("\\\\"),
+                        '"' => escaped.push_str("\\\""),
+                        '\n' => escaped.push_str("\\n"),
+                        '\r' => escaped.push_str("\\r"),
+                        '\t' => escaped.push_str("\\t"),
+                        c if (c as u32) < 0x20 => {
+                            escaped.push_str(&format!("\\u{:04x}", c as u32))
+                        }
+                        c => escaped.push(c),
+                    }
+                }
+                let data = escaped;
+                writeln!(
+                    self.writer,
+                    r#"{{"ts":{:.3},"mono":{:.3},"dir":"{}","data":"{}"}}"#,
+                    wall, mono, dir, data
+                )?;
+            }
+        }
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Where to read scripted AT commands from, as given to `--script`.
+enum ScriptSource {
+    Stdin,
+    File(std::path::PathBuf),
+}
+
+/// Settings for non-interactive (`--script`) sessions.
+struct ScriptConfig {
+    source: ScriptSource,
+    /// How long to wait for a reply after sending a command.
+    delay: Duration,
+}
+
+/// Drains any already-queued central events, returning whether `addr` was
+/// reported lost or disconnected. Used by the scripted runner, which has no
+/// `select!` loop to notice this as it happens.
+fn device_disconnected(bt_receiver: &c_channel::Receiver<CentralEvent>, addr: BDAddr) -> bool {
+    while let Ok(event) = bt_receiver.try_recv() {
+        match event {
+            CentralEvent::DeviceLost(a) | CentralEvent::DeviceDisconnected(a) if a == addr => {
+                return true;
+            }
+            _ => (),
+        }
+    }
+    false
+}
+
+/// Settings for `--reconnect`.
+struct ReconnectConfig {
+    /// `None` means retry forever.
+    max_attempts: Option<u32>,
+}
+
+/// Re-runs the scan/connect/subscribe cycle for `addr` after a drop, with
+/// exponential backoff between attempts, reusing the same helpers the
+/// initial connection went through. Keeps retrying (capped by
+/// `cfg.max_attempts` if set) until the device is back and the same profile
+/// is matched again.
+fn reconnect_device<P: Peripheral, C: Central<P>>(
+    central: &C,
+    bt_receiver: &c_channel::Receiver<CentralEvent>,
+    ctclc_receiver: &c_channel::Receiver<()>,
+    addr: BDAddr,
+    profile_kind: ProfileKind,
+    cfg: &ReconnectConfig,
+    printer: &Arc<Mutex<Box<dyn ExternalPrinter + Send>>>,
+) -> Result<(P, MatchedProfile), Error> {
+    if let Ok(mut printer) = printer.lock() {
+        let _ = printer.print("[RECONNECTING]\n".to_string());
+    }
+    let max_backoff = Duration::from_secs(30);
+    let mut backoff = Duration::from_millis(500);
+    let mut attempt = 0u32;
+    loop {
+        attempt += 1;
+        if cfg.max_attempts.map_or(false, |max| attempt > max) {
+            return Err(Error::ReconnectFailed);
+        }
+
+        central.start_scan()?;
+        let found = find_device(central, &addr, bt_receiver, ctclc_receiver)?;
+        central.stop_scan()?;
+        let device = match found {
+            Some(device) => device,
+            // `find_device` only returns `None` when `ctclc_receiver` fired,
+            // i.e. the user hit Ctrl+C. Bail instead of looping forever on a
+            // drained channel that will never fire again.
+            None => return Err(Error::ReconnectCancelled),
+        };
+        if keep_connect(&device).is_ok() {
+            if let Ok(characteristics) = device.discover_characteristics() {
+                if let Some(matched) = match_profile(&characteristics, Some(profile_kind)) {
+                    if let Ok(mut printer) = printer.lock() {
+                        let _ = printer.print("[RECONNECTED]\n".to_string());
+                    }
+                    return Ok((device, matched));
+                }
+            }
+        }
+
+        thread::sleep(backoff);
+        backoff = std::cmp::min(backoff * 2, max_backoff);
+    }
+}
+
+fn run_console<P: Peripheral, C: Central<P>>(
+    central: &C,
     bt_receiver: c_channel::Receiver<CentralEvent>,
     ctclc_receiver: c_channel::Receiver<()>,
     device: P,
+    profile: Option<ProfileKind>,
+    script: Option<ScriptConfig>,
+    log: Option<LogConfig>,
+    reconnect: Option<ReconnectConfig>,
 ) -> Result<(), Error> {
     println!("Connecting to {}", device.address());
     keep_connect(&device)?;
@@ -290,31 +744,87 @@ fn run_console<P: Peripheral>(
     );
     println!("Connected: {}", name);
     let characteristics = device.discover_characteristics()?;
-    if !characteristics.iter().any(|c| c.uuid == UUID_NOTIFY) {
-        return Err(Error::NotHMDevice);
+    let matched = match_profile(&characteristics, profile).ok_or_else(|| {
+        let searched = profile.map_or_else(
+            || PROFILES.iter().map(|p| p.kind).collect::<Vec<_>>(),
+            |kind| vec![kind],
+        );
+        Error::NoMatchingProfile(
+            searched
+                .into_iter()
+                .map(ProfileKind::as_str)
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    })?;
+    println!("Using {} profile", matched.kind.as_str());
+
+    let logger = log
+        .map(|cfg| SessionLogger::new(&cfg.path, cfg.format))
+        .transpose()?
+        .map(|logger| Arc::new(Mutex::new(logger)));
+
+    match script {
+        Some(script) => run_scripted(device, &matched, &bt_receiver, script, logger),
+        None => run_interactive(
+            central,
+            bt_receiver,
+            ctclc_receiver,
+            device,
+            matched,
+            logger,
+            reconnect,
+        ),
     }
+}
 
-    device.on_notification(Box::new(|notification: ValueNotification| {
+/// Subscribes to notifications on `device`, routing inbound text through
+/// `printer` (and, if set, `logger`) rather than printing directly, so a
+/// resubscribe after a reconnect behaves identically to the first one.
+fn subscribe_notifications<P: Peripheral>(
+    device: &P,
+    matched: &MatchedProfile,
+    printer: &Arc<Mutex<Box<dyn ExternalPrinter + Send>>>,
+    logger: &Option<Arc<Mutex<SessionLogger>>>,
+) -> Result<(), Error> {
+    let notify_printer = printer.clone();
+    let notify_logger = logger.clone();
+    device.on_notification(Box::new(move |notification: ValueNotification| {
         let value = notification.value.clone();
         if value.len() > 0 {
-            println!(
-                "{}",
-                match String::from_utf8(value) {
-                    Ok(s) => s,
-                    Err(_) => format!("Failed to decode message: {:x?}", notification.value),
+            let text = match String::from_utf8(value) {
+                Ok(s) => s,
+                Err(_) => format!("Failed to decode message: {:x?}", notification.value),
+            };
+            if let Some(logger) = &notify_logger {
+                if let Ok(mut logger) = logger.lock() {
+                    let _ = logger.log(LogDirection::In, &text);
                 }
-            );
+            }
+            if let Ok(mut printer) = notify_printer.lock() {
+                let _ = printer.print(format!("{}\n", text));
+            }
         }
     }));
-    let notify_service = characteristics
-        .iter()
-        .find(|c| c.uuid == UUID_NOTIFY)
-        .unwrap();
-
-    device.subscribe(&notify_service)?;
+    device.subscribe(&matched.notify)?;
+    Ok(())
+}
 
+fn run_interactive<P: Peripheral, C: Central<P>>(
+    central: &C,
+    bt_receiver: c_channel::Receiver<CentralEvent>,
+    ctclc_receiver: c_channel::Receiver<()>,
+    mut device: P,
+    mut matched: MatchedProfile,
+    logger: Option<Arc<Mutex<SessionLogger>>>,
+    reconnect: Option<ReconnectConfig>,
+) -> Result<(), Error> {
     let (sync_sender, sync_receiver) = unbounded();
-    let prompt_receiver = create_prompt_channel(sync_receiver);
+    let (prompt_receiver, printer) = create_prompt_channel(sync_receiver)?;
+    let printer = Arc::new(Mutex::new(printer));
+
+    subscribe_notifications(&device, &matched, &printer, &logger)?;
+    let mut pending_command: Option<String> = None;
 
     loop {
         c_channel::select! {
@@ -324,8 +834,42 @@ fn run_console<P: Peripheral>(
                       CentralEvent::DeviceLost(addr)
                     | CentralEvent::DeviceDisconnected(addr)
                     if addr == device.address() => {
-                        println!("Device disconnected!");
-                        break;
+                        match &reconnect {
+                            Some(cfg) => {
+                                let (new_device, new_matched) = match reconnect_device(
+                                    central,
+                                    &bt_receiver,
+                                    &ctclc_receiver,
+                                    addr,
+                                    matched.kind,
+                                    cfg,
+                                    &printer,
+                                ) {
+                                    Ok(result) => result,
+                                    Err(Error::ReconnectCancelled) => break,
+                                    Err(err) => return Err(err),
+                                };
+                                device = new_device;
+                                matched = new_matched;
+                                subscribe_notifications(&device, &matched, &printer, &logger)?;
+                                if let Some(command) = pending_command.take() {
+                                    let mut resent = true;
+                                    for chunk in command.as_bytes().chunks(20) {
+                                        if device.command(&matched.write, chunk).is_err() {
+                                            resent = false;
+                                            break;
+                                        }
+                                    }
+                                    if !resent {
+                                        pending_command = Some(command);
+                                    }
+                                }
+                            }
+                            None => {
+                                println!("Device disconnected!");
+                                break;
+                            }
+                        }
                     }
                     _ => (),
                 }
@@ -340,8 +884,23 @@ fn run_console<P: Peripheral>(
                     device.disconnect()?;
                     break;
                 }
+                if let Some(logger) = &logger {
+                    if let Ok(mut logger) = logger.lock() {
+                        let _ = logger.log(LogDirection::Out, &command);
+                    }
+                }
+                let mut sent = true;
                 for chunk in command.as_bytes().chunks(20) {
-                    device.command(&notify_service, chunk)?;
+                    if let Err(err) = device.command(&matched.write, chunk) {
+                        if reconnect.is_none() {
+                            return Err(Error::from(err));
+                        }
+                        sent = false;
+                        break;
+                    }
+                }
+                if !sent {
+                    pending_command = Some(command);
                 }
                 thread::sleep(Duration::from_millis(10));
                 sync_sender.send(()).unwrap();
@@ -351,7 +910,93 @@ fn run_console<P: Peripheral>(
     Ok(())
 }
 
-fn run_connect(addr: &str) -> Result<(), Error> {
+/// Feeds AT commands from `script` to the device one at a time, printing
+/// each command's reply prefixed with the command that produced it. Exits
+/// with an error if the device disconnects mid-run or a command elicits an
+/// `ERROR` response, so the process's exit status reflects success.
+fn run_scripted<P: Peripheral>(
+    device: P,
+    matched: &MatchedProfile,
+    bt_receiver: &c_channel::Receiver<CentralEvent>,
+    script: ScriptConfig,
+    logger: Option<Arc<Mutex<SessionLogger>>>,
+) -> Result<(), Error> {
+    let (notify_sender, notify_receiver) = unbounded();
+    device.on_notification(Box::new(move |notification: ValueNotification| {
+        let value = notification.value.clone();
+        if value.len() > 0 {
+            let text = match String::from_utf8(value) {
+                Ok(s) => s,
+                Err(_) => format!("Failed to decode message: {:x?}", notification.value),
+            };
+            let _ = notify_sender.send(text);
+        }
+    }));
+    device.subscribe(&matched.notify)?;
+
+    let stdin = std::io::stdin();
+    let lines: Box<dyn Iterator<Item = std::io::Result<String>>> = match &script.source {
+        ScriptSource::Stdin => Box::new(stdin.lock().lines()),
+        ScriptSource::File(path) => {
+            Box::new(std::io::BufReader::new(std::fs::File::open(path)?).lines())
+        }
+    };
+
+    for line in lines {
+        let command = line?;
+        let command = command.trim();
+        if command.is_empty() {
+            continue;
+        }
+        if device_disconnected(bt_receiver, device.address()) {
+            return Err(Error::ScriptDisconnected);
+        }
+
+        if let Some(logger) = &logger {
+            if let Ok(mut logger) = logger.lock() {
+                let _ = logger.log(LogDirection::Out, command);
+            }
+        }
+        for chunk in command.as_bytes().chunks(20) {
+            device.command(&matched.write, chunk)?;
+        }
+
+        let deadline = Instant::now() + script.delay;
+        let mut reply = String::new();
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            match notify_receiver.recv_timeout(remaining) {
+                Ok(text) => reply.push_str(&text),
+                Err(_) => break,
+            }
+        }
+        println!("> {}", command);
+        if !reply.is_empty() {
+            println!("< {}", reply);
+            if let Some(logger) = &logger {
+                if let Ok(mut logger) = logger.lock() {
+                    let _ = logger.log(LogDirection::In, &reply);
+                }
+            }
+        }
+        if reply.contains("ERROR") {
+            return Err(Error::ScriptCommandError(command.to_string()));
+        }
+        if device_disconnected(bt_receiver, device.address()) {
+            return Err(Error::ScriptDisconnected);
+        }
+    }
+
+    device.disconnect()?;
+    Ok(())
+}
+
+fn run_connect(
+    addr: &str,
+    profile: Option<ProfileKind>,
+    script: Option<ScriptConfig>,
+    log: Option<LogConfig>,
+    reconnect: Option<ReconnectConfig>,
+) -> Result<(), Error> {
     let device_addr = BDAddr::from_str(addr)?;
 
     let manager = Manager::new()?;
@@ -366,7 +1011,16 @@ fn run_connect(addr: &str) -> Result<(), Error> {
     let device = find_device(&central, &device_addr, &bt_receiver, &ctrlc_receiver)?;
     central.stop_scan()?;
     if let Some(device) = device {
-        run_console(bt_receiver, ctrlc_receiver, device)?;
+        run_console(
+            &central,
+            bt_receiver,
+            ctrlc_receiver,
+            device,
+            profile,
+            script,
+            log,
+            reconnect,
+        )?;
     }
     println!("Bye!");
 
@@ -386,13 +1040,19 @@ fn main() -> Result<(), Error> {
                     Arg::with_name("verbose")
                         .short("v")
                         .long("verbose")
-                        .help("Displays BLE device update"),
+                        .help("Displays BLE device update, manufacturer data included"),
                 )
                 .arg(
-                    Arg::with_name("filter-unnamed")
-                        .short("f")
-                        .long("filter-unnamed")
-                        .help("Only displays BLE device with a name"),
+                    Arg::with_name("min-rssi")
+                        .long("min-rssi")
+                        .takes_value(true)
+                        .help("Only displays BLE device with at least this RSSI, in dBm"),
+                )
+                .arg(
+                    Arg::with_name("name")
+                        .long("name")
+                        .takes_value(true)
+                        .help("Only displays BLE device whose name matches this regex"),
                 ),
         )
         .subcommand(
@@ -402,18 +1062,117 @@ fn main() -> Result<(), Error> {
                     Arg::with_name("ADDRESS")
                         .required(true)
                         .help("The MAC address of the device to connect"),
+                )
+                .arg(
+                    Arg::with_name("profile")
+                        .long("profile")
+                        .takes_value(true)
+                        .possible_values(&["hm", "nus", "auto"])
+                        .default_value("auto")
+                        .help("The serial-over-BLE profile to use"),
+                )
+                .arg(
+                    Arg::with_name("script")
+                        .long("script")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Reads AT commands from FILE (- for stdin) instead of prompting"),
+                )
+                .arg(
+                    Arg::with_name("script-delay")
+                        .long("script-delay")
+                        .takes_value(true)
+                        .default_value("200")
+                        .requires("script")
+                        .help("Milliseconds to wait for a reply after each scripted command"),
+                )
+                .arg(
+                    Arg::with_name("log")
+                        .long("log")
+                        .takes_value(true)
+                        .value_name("FILE")
+                        .help("Records the session, with timestamps, to FILE"),
+                )
+                .arg(
+                    Arg::with_name("log-format")
+                        .long("log-format")
+                        .takes_value(true)
+                        .possible_values(&["text", "jsonl"])
+                        .default_value("text")
+                        .requires("log")
+                        .help("Format used by --log"),
+                )
+                .arg(
+                    Arg::with_name("reconnect")
+                        .long("reconnect")
+                        .takes_value(true)
+                        .min_values(0)
+                        .require_equals(true)
+                        .value_name("max-attempts")
+                        .conflicts_with("script")
+                        .help("Reconnects on disconnect, optionally capped at <max-attempts>. Not supported with --script"),
                 ),
         )
         .get_matches();
 
     if let Some(matches) = cmd.subcommand_matches("scan") {
-        let (verbose, filter_unnamed) = (
-            matches.is_present("verbose"),
-            matches.is_present("filter-unnamed"),
-        );
-        run_scan(verbose, filter_unnamed)
+        let verbose = matches.is_present("verbose");
+        let min_rssi = matches
+            .value_of("min-rssi")
+            .map(str::parse::<i8>)
+            .transpose()?;
+        let name_filter = matches.value_of("name").map(Regex::new).transpose()?;
+        run_scan(verbose, min_rssi, name_filter)
     } else if let Some(matches) = cmd.subcommand_matches("connect") {
-        run_connect(matches.value_of("ADDRESS").unwrap())
+        let profile = match matches.value_of("profile").unwrap() {
+            "auto" => None,
+            kind => Some(ProfileKind::from_str(kind)?),
+        };
+        let script = matches
+            .value_of("script")
+            .map(|path| {
+                let source = if path == "-" {
+                    ScriptSource::Stdin
+                } else {
+                    ScriptSource::File(std::path::PathBuf::from(path))
+                };
+                let delay_ms: u64 = matches
+                    .value_of("script-delay")
+                    .unwrap()
+                    .parse()
+                    .map_err(|e| Error::InvalidInteger("--script-delay", e))?;
+                Ok::<_, Error>(ScriptConfig {
+                    source,
+                    delay: Duration::from_millis(delay_ms),
+                })
+            })
+            .transpose()?;
+        let log = matches
+            .value_of("log")
+            .map(|path| {
+                Ok::<_, Error>(LogConfig {
+                    path: std::path::PathBuf::from(path),
+                    format: LogFormat::from_str(matches.value_of("log-format").unwrap())?,
+                })
+            })
+            .transpose()?;
+        let reconnect = if matches.is_present("reconnect") {
+            let max_attempts = matches
+                .value_of("reconnect")
+                .map(str::parse::<u32>)
+                .transpose()
+                .map_err(|e| Error::InvalidInteger("--reconnect", e))?;
+            Some(ReconnectConfig { max_attempts })
+        } else {
+            None
+        };
+        run_connect(
+            matches.value_of("ADDRESS").unwrap(),
+            profile,
+            script,
+            log,
+            reconnect,
+        )
     } else {
         unreachable!()
     }